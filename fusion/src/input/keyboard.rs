@@ -0,0 +1,245 @@
+use super::{Datamap, InputMethod};
+use crate::{
+	node::{HandledNodeType, Node, NodeError, NodeType},
+	spatial::Spatial,
+	HandlerWrapper,
+};
+use anyhow::Result;
+use parking_lot::Mutex;
+use stardust_xr::{schemas::flex::flexbuffers, values::Transform};
+use std::{ops::Deref, sync::Arc};
+
+/// A single key transition carried in a `KeyboardInputMethod`'s per-frame datamap.
+#[derive(Debug, Clone, Copy)]
+pub struct KeyTransition {
+	/// XKB keycode (evdev keycode + 8) of the key that changed.
+	pub keycode: u32,
+	/// `true` if the key went down this frame, `false` if it went up.
+	pub pressed: bool,
+}
+
+/// A decoded frame of keyboard state, as written into `InputData::datamap` by
+/// [`KeyboardInputMethod::set_keys`].
+///
+/// `InputDataType` (the spatial-shape enum on `InputData`) has no `Keyboard` discriminant, so an
+/// `InputHandlerHandler` can't tell from `data.input` alone that a method is a keyboard. Track the
+/// UIDs of the `KeyboardInputMethod`s you care about (e.g. from app-level bookkeeping, or because
+/// you created them yourself) and call [`KeyboardFrame::decode`] on `data.datamap` once you know.
+#[derive(Debug, Clone, Default)]
+pub struct KeyboardFrame {
+	/// The modifier mask for this frame.
+	pub modifiers: u32,
+	/// Keys that changed state this frame.
+	pub keys: Vec<KeyTransition>,
+}
+impl KeyboardFrame {
+	/// Decode the modifier mask and key transitions written by [`KeyboardInputMethod::set_keys`].
+	///
+	/// Returns `None` if the datamap doesn't match the expected shape (e.g. it's from a
+	/// non-keyboard method).
+	pub fn decode(datamap: &Datamap) -> Option<Self> {
+		datamap
+			.with_data(|map| {
+				let modifiers = map.idx("modifiers").as_u64() as u32;
+				let keys = map
+					.idx("keys")
+					.get_vector()
+					.ok()?
+					.iter()
+					.filter_map(|key| {
+						let key = key.get_map().ok()?;
+						Some(KeyTransition {
+							keycode: key.idx("keycode").as_u64() as u32,
+							pressed: key.idx("pressed").get_bool().ok()?,
+						})
+					})
+					.collect();
+				Some(KeyboardFrame { modifiers, keys })
+			})
+			.flatten()
+	}
+}
+
+/// React to this method's keyboard focus changing, i.e. which handler (if any) has it captured.
+pub trait KeyboardInputMethodHandler: Send + Sync {
+	/// `handler_uid` captured this method and now holds keyboard focus. Show focus UI here.
+	fn focus_gained(&mut self, _handler_uid: &str) {}
+	/// This method lost keyboard focus, because the capturing handler released it or another
+	/// handler captured it instead. The method's datamap is reset automatically (see
+	/// [`KeyboardInputMethod::reset`]).
+	fn focus_lost(&mut self, _handler_uid: &str) {}
+}
+
+/// Virtual spatial input device representing a keyboard, propagating an XKB keymap.
+///
+/// The keymap string is uploaded once at creation and handlers that capture this method
+/// effectively hold keyboard focus (capture already expresses exclusivity). Each frame the
+/// method's datamap carries the current modifier mask and the list of key transitions, decoded
+/// on the handler side with [`KeyboardFrame::decode`].
+#[derive(Debug)]
+pub struct KeyboardInputMethod {
+	spatial: Spatial,
+}
+impl<'a> KeyboardInputMethod {
+	pub fn create(
+		spatial_parent: &'a Spatial,
+		transform: Transform,
+		keymap: &str,
+	) -> Result<Self, NodeError> {
+		let id = nanoid::nanoid!();
+		Ok(KeyboardInputMethod {
+			spatial: Spatial {
+				node: Node::new(
+					&spatial_parent.node.client()?,
+					"/input",
+					"create_input_method_keyboard",
+					"/input/method/keyboard",
+					true,
+					&id.clone(),
+					(
+						id,
+						spatial_parent.node().get_path()?,
+						transform,
+						keymap,
+					),
+				)?,
+			},
+		})
+	}
+
+	/// Upload a new XKB keymap, e.g. when the layout changes.
+	pub fn set_keymap(&self, keymap: &str) -> Result<(), NodeError> {
+		self.node.send_remote_signal("set_keymap", &keymap)
+	}
+
+	/// Zero the modifier mask and clear the key transitions in the datamap, e.g. when this method
+	/// loses keyboard focus. No release transitions are sent for keys that are still down.
+	pub fn reset(&self) -> Result<(), NodeError> {
+		self.set_keys(0, &[])
+	}
+
+	/// Set this frame's modifier mask and key transitions in the method's datamap.
+	pub fn set_keys(&self, modifiers: u32, keys: &[KeyTransition]) -> Result<(), NodeError> {
+		let mut fbb = flexbuffers::Builder::default();
+		let mut map = fbb.start_map();
+		map.push("modifiers", modifiers);
+		let mut list = map.start_vector("keys");
+		for key in keys {
+			let mut key_map = list.start_map();
+			key_map.push("keycode", key.keycode);
+			key_map.push("pressed", key.pressed);
+			key_map.end_map();
+		}
+		list.end_vector();
+		map.end_map();
+		self.set_datamap(fbb.view())
+	}
+
+	/// Wrap this method and a `KeyboardInputMethodHandler` to get focus push notifications.
+	///
+	/// The datamap is reset with [`KeyboardInputMethod::reset`] automatically whenever focus is
+	/// lost.
+	#[must_use = "Dropping this handler wrapper would immediately drop the node"]
+	pub fn wrap<H: KeyboardInputMethodHandler>(
+		self,
+		handler: H,
+	) -> Result<HandlerWrapper<Self, H>, NodeError> {
+		self.wrap_raw(Arc::new(Mutex::new(handler)))
+	}
+	/// Wrap this method and a `KeyboardInputMethodHandler` to get focus push notifications.
+	#[must_use = "Dropping this handler wrapper would immediately drop the node"]
+	pub fn wrap_raw<H: KeyboardInputMethodHandler>(
+		self,
+		handler: Arc<Mutex<H>>,
+	) -> Result<HandlerWrapper<Self, H>, NodeError> {
+		let handler_wrapper = HandlerWrapper::new_raw(self, handler);
+		handler_wrapper.add_handled_signal("focus_gained", Self::handle_focus_gained)?;
+		handler_wrapper.add_handled_signal("focus_lost", Self::handle_focus_lost)?;
+		Ok(handler_wrapper)
+	}
+
+	fn handle_focus_gained<H: KeyboardInputMethodHandler>(
+		_method: Arc<KeyboardInputMethod>,
+		handler: Arc<Mutex<H>>,
+		data: &[u8],
+	) -> color_eyre::eyre::Result<()> {
+		let uid: String = stardust_xr::schemas::flex::deserialize(data)?;
+		handler.lock().focus_gained(&uid);
+		Ok(())
+	}
+	fn handle_focus_lost<H: KeyboardInputMethodHandler>(
+		method: Arc<KeyboardInputMethod>,
+		handler: Arc<Mutex<H>>,
+		data: &[u8],
+	) -> color_eyre::eyre::Result<()> {
+		let uid: String = stardust_xr::schemas::flex::deserialize(data)?;
+		let _ = method.reset();
+		handler.lock().focus_lost(&uid);
+		Ok(())
+	}
+}
+impl InputMethod for KeyboardInputMethod {
+	fn node(&self) -> &Node {
+		&self.node
+	}
+}
+impl NodeType for KeyboardInputMethod {
+	fn node(&self) -> &Node {
+		&self.spatial.node
+	}
+
+	fn alias(&self) -> Self {
+		KeyboardInputMethod {
+			spatial: self.spatial.alias(),
+		}
+	}
+}
+impl HandledNodeType for KeyboardInputMethod {}
+impl Deref for KeyboardInputMethod {
+	type Target = Spatial;
+
+	fn deref(&self) -> &Self::Target {
+		&self.spatial
+	}
+}
+
+#[tokio::test]
+async fn fusion_keyboard_input_method() {
+	use crate::client::Client;
+
+	let (client, event_loop) = Client::connect_with_async_loop()
+		.await
+		.expect("Couldn't connect");
+
+	// Empty placeholder keymap (no layout); a real client uploads the string from
+	// `xkb_keymap_get_as_string`. This just exercises the create/set_keys plumbing.
+	let keymap = "xkb_keymap {};";
+	let keyboard =
+		KeyboardInputMethod::create(client.get_root(), Transform::default(), keymap).unwrap();
+	keyboard
+		.set_keys(
+			0,
+			&[KeyTransition {
+				keycode: 65,
+				pressed: true,
+			}],
+		)
+		.unwrap();
+
+	struct KeyboardFocusTest;
+	impl KeyboardInputMethodHandler for KeyboardFocusTest {
+		fn focus_gained(&mut self, uid: &str) {
+			dbg!(uid);
+		}
+		fn focus_lost(&mut self, uid: &str) {
+			dbg!(uid);
+		}
+	}
+	let _keyboard = keyboard.wrap(KeyboardFocusTest).unwrap();
+
+	tokio::select! {
+		biased;
+		_ = tokio::signal::ctrl_c() => (),
+		e = event_loop => e.unwrap().unwrap(),
+	};
+}
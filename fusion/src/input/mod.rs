@@ -9,20 +9,31 @@
 //! The return value for `InputHandlerHandler::input` is `true` if you want to capture the input method.
 //! Capturing an input method is useful to indicate that only that handler should get its input.
 //! For example when grabbing you don't want your hand to press buttons if you're grabbing the object through them.
-//! Input handlers should account for the occasional case where their field is closer than an input handler that captured a method by filtering out interactions that are triggered the same frame the input method first becomes visible.
-//! Capturing an input method may be delayed a frame or 2.
+//! Capturing an input method may be delayed a frame or 2; where exact precedence matters more
+//! than distance, the input method's owner can fix the order with `InputMethod::set_handler_order`,
+//! which overrides the distance order below, instead of handlers filtering same-frame
+//! interactions by hand.
 //!
 //! Every frame, the server will do this for each input method:
 //! - Sort the input handlers by the distance from the input method to their fields (often absolute value for onion skinning)
 //! - Send out input events (`InputHandlerHandler::input`) in order of distance until an input handler has captured the method.
 //! - The frame event is sent (`LifeCycle::frame`).
 //!
+//! `InputData` is re-exported wholesale from the external `stardust_xr` schema crate, so it
+//! currently only carries the plain `distance`. Surfacing the onion-skin signed distance,
+//! closest point, and normal alongside it needs a schema change upstream in that crate; it's out
+//! of scope for this crate until that lands, not something `fusion` can add on its own.
+//!
 //! To make this all easier, the `action` module exists, check it out.
 
 pub mod action;
+pub mod data;
+mod keyboard;
 mod tip;
 
 pub use action as action_handler;
+pub use data::{DataAction, DataOffer, DataSource, DataSourceHandler, Persistence};
+pub use keyboard::{KeyTransition, KeyboardFrame, KeyboardInputMethod, KeyboardInputMethodHandler};
 pub use stardust_xr::schemas::flat::*;
 pub use tip::TipInputMethod;
 
@@ -43,6 +54,12 @@ pub trait InputHandlerHandler: Send + Sync {
 	///
 	/// Return "true" to capture the input method or "false" to not.
 	fn input(&mut self, input: UnknownInputMethod, data: InputData);
+
+	/// This handler now holds the method with the given UID (capture may lag a frame or 2).
+	fn capture_gained(&mut self, _uid: &str) {}
+	/// This handler no longer holds the method with the given UID, because it left the field
+	/// or another handler captured it. Release any state keyed on that method here.
+	fn capture_lost(&mut self, _uid: &str) {}
 }
 
 /// Node representing a spatial input device.
@@ -54,10 +71,38 @@ pub trait InputMethod {
 			.map_err(|_| NodeError::MapInvalid)?;
 		self.node().send_remote_signal_raw("set_datamap", datamap)
 	}
+
+	/// Override the order in which handlers receive this method's input.
+	///
+	/// The server normally sorts handlers by field distance each frame, which makes
+	/// capture precedence non-deterministic when two fields overlap. Naming handlers here
+	/// forces `InputHandlerHandler::input` to be delivered to them in exactly this sequence,
+	/// stopping at the first that captures. Handlers not in the list fall back to distance
+	/// order after the explicit ones. Pass an empty slice to return to pure distance order.
+	fn set_handler_order(&self, handlers: &[&InputHandler]) -> Result<(), NodeError> {
+		let order = handlers
+			.iter()
+			.map(|handler| handler.node().get_path())
+			.collect::<Result<Vec<_>, _>>()?;
+		self.node().send_remote_signal("set_handler_order", &order)
+	}
+	/// Append a handler to the end of the explicit order set by [`set_handler_order`].
+	///
+	/// [`set_handler_order`]: InputMethod::set_handler_order
+	fn add_handler_order(&self, handler: &InputHandler) -> Result<(), NodeError> {
+		self.node()
+			.send_remote_signal("add_handler_order", &handler.node().get_path()?)
+	}
+	/// Remove a handler from the explicit order, letting it fall back to distance order.
+	fn remove_handler_order(&self, handler: &InputHandler) -> Result<(), NodeError> {
+		self.node()
+			.send_remote_signal("remove_handler_order", &handler.node().get_path()?)
+	}
 }
 
 pub struct UnknownInputMethod {
 	spatial: Spatial,
+	uid: String,
 	handler: Arc<InputHandler>,
 }
 impl UnknownInputMethod {
@@ -66,6 +111,7 @@ impl UnknownInputMethod {
 			spatial: Spatial {
 				node: Node::from_path(&handler.client()?, handler.node().get_path()?, uid, false),
 			},
+			uid: uid.to_string(),
 			handler,
 		})
 	}
@@ -73,6 +119,18 @@ impl UnknownInputMethod {
 		self.node()
 			.send_remote_signal("capture", &self.handler.node().get_path()?)
 	}
+	/// Get the path of the handler currently capturing this method, or `None` if uncaptured.
+	pub async fn captured_by(&self) -> Result<Option<String>, NodeError> {
+		self.node().execute_remote_method("captured_by", &()).await
+	}
+	/// Get the data offer this method's owner published, if any, to receive transferred content.
+	///
+	/// Only valid once this handler has captured the method (see [`capture`]).
+	///
+	/// [`capture`]: UnknownInputMethod::capture
+	pub fn data_offer(&self) -> Result<data::DataOffer, NodeError> {
+		data::DataOffer::from_path(&self.handler, &self.uid)
+	}
 }
 impl NodeType for UnknownInputMethod {
 	fn node(&self) -> &Node {
@@ -82,6 +140,7 @@ impl NodeType for UnknownInputMethod {
 	fn alias(&self) -> Self {
 		UnknownInputMethod {
 			spatial: self.spatial.alias(),
+			uid: self.uid.clone(),
 			handler: self.handler.clone(),
 		}
 	}
@@ -145,9 +204,37 @@ impl<'a> InputHandler {
 	) -> Result<HandlerWrapper<Self, H>, NodeError> {
 		let handler_wrapper = HandlerWrapper::new_raw(self, handler);
 		handler_wrapper.add_handled_signal("input", Self::handle_input)?;
+		handler_wrapper.add_handled_signal("capture_gained", Self::handle_capture_gained)?;
+		handler_wrapper.add_handled_signal("capture_lost", Self::handle_capture_lost)?;
 		Ok(handler_wrapper)
 	}
 
+	/// Get the set of method UIDs this handler currently holds captured.
+	pub async fn captured_methods(&self) -> Result<Vec<String>, NodeError> {
+		self.node()
+			.execute_remote_method("captured_methods", &())
+			.await
+	}
+
+	fn handle_capture_gained<H: InputHandlerHandler>(
+		_input_handler: Arc<InputHandler>,
+		handler: Arc<Mutex<H>>,
+		data: &[u8],
+	) -> color_eyre::eyre::Result<()> {
+		let uid: String = stardust_xr::schemas::flex::deserialize(data)?;
+		handler.lock().capture_gained(&uid);
+		Ok(())
+	}
+	fn handle_capture_lost<H: InputHandlerHandler>(
+		_input_handler: Arc<InputHandler>,
+		handler: Arc<Mutex<H>>,
+		data: &[u8],
+	) -> color_eyre::eyre::Result<()> {
+		let uid: String = stardust_xr::schemas::flex::deserialize(data)?;
+		handler.lock().capture_lost(&uid);
+		Ok(())
+	}
+
 	fn handle_input<H: InputHandlerHandler>(
 		input_handler: Arc<InputHandler>,
 		handler: Arc<Mutex<H>>,
@@ -195,9 +282,13 @@ async fn fusion_input_handler() {
 
 	struct InputHandlerTest;
 	impl InputHandlerHandler for InputHandlerTest {
-		fn input(&mut self, _input: UnknownInputMethod, data: InputData) {
+		fn input(&mut self, input: UnknownInputMethod, data: InputData) {
 			dbg!(data.uid);
 			dbg!(data.distance);
+			// Exercise the chunk0-4 capture query from the owning handler's own input callback.
+			tokio::spawn(async move {
+				dbg!(input.captured_by().await);
+			});
 			match &data.input {
 				InputDataType::Pointer(_) => {
 					println!("Pointer input");
@@ -215,8 +306,18 @@ async fn fusion_input_handler() {
 				InputDataType::Tip(_) => {
 					println!("Tip input");
 				}
+				// InputDataType has no Keyboard discriminant: a handler that tracks a
+				// KeyboardInputMethod's UID separately decodes its datamap with
+				// `KeyboardFrame::decode` instead of matching here.
 			}
 		}
+
+		fn capture_gained(&mut self, uid: &str) {
+			dbg!(uid);
+		}
+		fn capture_lost(&mut self, uid: &str) {
+			dbg!(uid);
+		}
 	}
 
 	// let input_handler = InputHandler::builder()
@@ -226,11 +327,20 @@ async fn fusion_input_handler() {
 	// 	.build()
 	// 	.await
 	// 	.unwrap();
-	let _input_handler = InputHandler::create(client.get_root(), Transform::default(), &field)
+	let input_handler = InputHandler::create(client.get_root(), Transform::default(), &field)
 		.unwrap()
 		.wrap(InputHandlerTest)
 		.unwrap();
 
+	// Exercise the chunk0-4 capture query API.
+	dbg!(input_handler.captured_methods().await.unwrap());
+
+	// Exercise the chunk0-1 manual handler-order API on a method.
+	let tip = TipInputMethod::create(client.get_root(), Transform::default(), 0.1, None).unwrap();
+	tip.set_handler_order(&[&*input_handler]).unwrap();
+	tip.add_handler_order(&input_handler).unwrap();
+	tip.remove_handler_order(&input_handler).unwrap();
+
 	tokio::select! {
 		biased;
 		_ = tokio::signal::ctrl_c() => (),